@@ -0,0 +1,83 @@
+//! Layer the D-Bus relay on top of `Master`: emit a `grab` whenever the
+//! monitored clipboard or primary selection changes, and a `release` for both
+//! when monitoring stops.
+//!
+//! Run with `cargo run --example dbus_relay --features dbus`.
+
+extern crate clipboard_master;
+
+#[cfg(not(feature = "dbus"))]
+fn main() {
+    eprintln!("This example requires the `dbus` feature: cargo run --example dbus_relay --features dbus");
+}
+
+#[cfg(feature = "dbus")]
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    use std::sync::mpsc;
+    use std::thread;
+
+    use clipboard_master::dbus::{ClipboardRelay, Selection};
+    use clipboard_master::{CallbackResult, ClipboardHandler, Master};
+
+    const PATH: &str = "/org/rustdesk/ClipboardRelay";
+
+    // The synchronous clipboard callbacks forward each change to the async task
+    // that owns the D-Bus connection, which turns it into a `grab` signal.
+    struct Handler {
+        tx: mpsc::Sender<(Selection, Vec<String>)>,
+    }
+
+    impl ClipboardHandler for Handler {
+        fn on_clipboard_change(&mut self) -> CallbackResult {
+            CallbackResult::Next
+        }
+
+        fn on_clipboard_change_with_formats(&mut self, formats: &[String]) -> CallbackResult {
+            let _ = self.tx.send((Selection::Clipboard, formats.to_vec()));
+            CallbackResult::Next
+        }
+
+        fn on_primary_selection_change(&mut self) -> CallbackResult {
+            let _ = self.tx.send((Selection::Primary, Vec::new()));
+            CallbackResult::Next
+        }
+    }
+
+    let (tx, rx) = mpsc::channel::<(Selection, Vec<String>)>();
+
+    let mut master = Master::new(Handler { tx })?;
+    master.monitor_primary(true);
+    // The relay reads selection data lazily through the monitor's worker.
+    let access = master.clipboard_access()?;
+
+    let relay = thread::spawn(move || -> zbus::Result<()> {
+        zbus::block_on(async move {
+            let connection = zbus::connection::Builder::session()?
+                .name("org.rustdesk.ClipboardRelay")?
+                .serve_at(PATH, ClipboardRelay::new(access))?
+                .build()
+                .await?;
+
+            let iface = connection
+                .object_server()
+                .interface::<_, ClipboardRelay>(PATH)
+                .await?;
+            let emitter = iface.signal_emitter();
+
+            // Each monitored change becomes a `grab`; when the monitor stops the
+            // sender is dropped, the loop ends, and we relinquish both selections.
+            for (selection, mimes) in rx {
+                iface.get().await.announce(emitter, selection, mimes).await?;
+            }
+            ClipboardRelay::release(emitter, Selection::Clipboard).await?;
+            ClipboardRelay::release(emitter, Selection::Primary).await?;
+            Ok(())
+        })
+    });
+
+    master.run()?;
+    // Dropping the monitor drops the handler's sender, which ends the relay loop.
+    drop(master);
+    let _ = relay.join();
+    Ok(())
+}
@@ -0,0 +1,9 @@
+//! Cross-platform clipboard monitor.
+//!
+//! Implement [`ClipboardHandler`] and hand it to a [`Master`] to be notified
+//! whenever the system clipboard (or, optionally, the primary selection)
+//! changes.
+
+mod master;
+
+pub use master::*;
@@ -0,0 +1,150 @@
+//! Optional D-Bus session-bus relay for mirroring clipboard and primary
+//! selections to a separate process (e.g. the remote-desktop peer in this
+//! crate's parent project).
+//!
+//! Rather than shipping the raw bytes on every change, the relay follows a
+//! grab / request / release model:
+//!
+//! * when [`on_clipboard_change`](super::ClipboardHandler::on_clipboard_change)
+//!   fires, [`ClipboardRelay::grab`] emits a `Grab(selection, serial, mimes)`
+//!   signal advertising *what* is available without transferring it;
+//! * a peer calls [`ClipboardRelay::request`] to pull the actual data for one
+//!   of the advertised MIME types on demand;
+//! * [`ClipboardRelay::release`] is emitted when ownership is lost.
+//!
+//! The monotonically increasing `serial` lets the relay discard stale requests
+//! that race a newer grab, so a peer never receives data from a selection that
+//! has already been replaced.
+//!
+//! This module is gated behind the `dbus` feature and layered on top of the
+//! [`ClipboardAccess`](super::ClipboardAccess) read worker.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use blocking::unblock;
+use zbus::{interface, object_server::SignalEmitter, zvariant::Type};
+
+use super::ClipboardAccess;
+
+/// The two selection channels mirrored over the bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Type, serde::Serialize, serde::Deserialize)]
+#[zvariant(signature = "u")]
+#[repr(u32)]
+pub enum Selection {
+    Clipboard = 0,
+    Primary = 1,
+}
+
+/// D-Bus interface re-publishing monitored selections.
+///
+/// Register an instance on a [`zbus::Connection`] object server and drive
+/// [`grab`](Self::grab) / [`release`](Self::release) from the clipboard
+/// callback; peers interact via the `Request` method and the two signals.
+pub struct ClipboardRelay {
+    access: ClipboardAccess,
+    /// Serials are tracked per selection: a grab on the clipboard must not
+    /// invalidate an in-flight request for the primary selection (which has
+    /// not changed) and vice versa.
+    clipboard_serial: AtomicU64,
+    primary_serial: AtomicU64,
+}
+
+impl ClipboardRelay {
+    /// Create a relay that reads selection data through `access`.
+    pub fn new(access: ClipboardAccess) -> Self {
+        Self {
+            access,
+            clipboard_serial: AtomicU64::new(0),
+            primary_serial: AtomicU64::new(0),
+        }
+    }
+
+    /// The counter tracking the latest grab for `selection`.
+    fn serial_for(&self, selection: Selection) -> &AtomicU64 {
+        match selection {
+            Selection::Clipboard => &self.clipboard_serial,
+            Selection::Primary => &self.primary_serial,
+        }
+    }
+
+    /// The serial advertised by the most recent grab of `selection`.
+    fn current_serial(&self, selection: Selection) -> u64 {
+        self.serial_for(selection).load(Ordering::Acquire)
+    }
+
+    /// Announce a new selection, emitting a `Grab` signal with a fresh serial.
+    ///
+    /// Call this from the clipboard-change callback with the MIME types already
+    /// gathered by the backend (the Wayland `mime_types` vector). Returns the
+    /// serial assigned to this grab.
+    pub async fn announce(
+        &self,
+        emitter: &SignalEmitter<'_>,
+        selection: Selection,
+        mimes: Vec<String>,
+    ) -> zbus::Result<u64> {
+        // Publish the serial only once the grab signal has actually gone out;
+        // bumping it before emission would, on a failed emit, advance past any
+        // serial a peer ever saw and wedge every later request as stale.
+        let serial = self.current_serial(selection) + 1;
+        Self::grab(emitter, selection, serial, mimes).await?;
+        self.serial_for(selection).store(serial, Ordering::Release);
+        Ok(serial)
+    }
+}
+
+#[interface(name = "org.rustdesk.ClipboardRelay1")]
+impl ClipboardRelay {
+    /// Pull the data for one of the advertised MIME types.
+    ///
+    /// `serial` must match the serial of the latest grab *for `selection`*; a
+    /// mismatch means that selection has since changed and the request is
+    /// rejected as stale so the peer re-reads the newer grab instead of
+    /// receiving outdated bytes.
+    async fn request(
+        &self,
+        selection: Selection,
+        serial: u64,
+        mimes: Vec<String>,
+    ) -> zbus::fdo::Result<(String, Vec<u8>)> {
+        let current = self.current_serial(selection);
+        if serial != current {
+            return Err(zbus::fdo::Error::Failed(format!(
+                "stale request: serial {serial} != current {current}"
+            )));
+        }
+        // `load`/`load_primary` block on the worker for a full clipboard
+        // round-trip; run them off the async executor so other method and
+        // signal dispatch on this connection is not stalled in the meantime.
+        let access = self.access.clone();
+        unblock(move || {
+            for mime in mimes {
+                let data = match selection {
+                    Selection::Clipboard => access.load(&mime),
+                    Selection::Primary => access.load_primary(&mime),
+                };
+                // Try the next advertised type if this one cannot be served.
+                if let Ok(bytes) = data {
+                    return Ok((mime, bytes));
+                }
+            }
+            Err(zbus::fdo::Error::Failed(
+                "none of the requested MIME types could be served".into(),
+            ))
+        })
+        .await
+    }
+
+    /// Advertise a new selection without transferring its contents.
+    #[zbus(signal)]
+    async fn grab(
+        emitter: &SignalEmitter<'_>,
+        selection: Selection,
+        serial: u64,
+        mimes: Vec<String>,
+    ) -> zbus::Result<()>;
+
+    /// Signal that ownership of `selection` was lost.
+    #[zbus(signal)]
+    async fn release(emitter: &SignalEmitter<'_>, selection: Selection) -> zbus::Result<()>;
+}
@@ -0,0 +1,235 @@
+mod wayland;
+#[cfg(feature = "dbus")]
+pub mod dbus;
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+
+use calloop::channel;
+
+use wayland::{Command, SelectionKind, WlClipboardListener};
+
+/// Tells [`Master`] whether to keep listening after a callback returns.
+pub enum CallbackResult {
+    /// Keep monitoring.
+    Next,
+    /// Stop monitoring cleanly.
+    Stop,
+    /// Stop monitoring and surface `error` from [`Master::run`].
+    StopWithError(io::Error),
+}
+
+/// Sink for clipboard events; pass an implementation to [`Master::new`].
+pub trait ClipboardHandler {
+    /// Called whenever the clipboard changes.
+    fn on_clipboard_change(&mut self) -> CallbackResult;
+
+    /// Called whenever the clipboard changes, with the MIME types the backend
+    /// advertised for the new contents.
+    ///
+    /// The default implementation discards `formats` and forwards to
+    /// [`on_clipboard_change`](Self::on_clipboard_change); override it to
+    /// filter changes (e.g. only react to `text/plain` versus an image or
+    /// files) without re-reading the clipboard on every change.
+    fn on_clipboard_change_with_formats(&mut self, _formats: &[String]) -> CallbackResult {
+        self.on_clipboard_change()
+    }
+
+    /// Called whenever the primary (middle-click) selection changes, provided
+    /// primary monitoring was enabled with [`Master::monitor_primary`].
+    ///
+    /// Defaults to a no-op that keeps listening.
+    fn on_primary_selection_change(&mut self) -> CallbackResult {
+        CallbackResult::Next
+    }
+
+    /// Called when the monitor hits an error. Defaults to stopping.
+    fn on_clipboard_error(&mut self, _error: io::Error) -> CallbackResult {
+        CallbackResult::Stop
+    }
+}
+
+/// A cloneable handle used to stop a running [`Master`] from another thread.
+#[derive(Clone)]
+pub struct Shutdown {
+    sender: Option<channel::Sender<()>>,
+    flag: Arc<AtomicBool>,
+}
+
+impl Shutdown {
+    /// Ask the associated [`Master::run`] loop to stop at the next opportunity.
+    pub fn signal(&self) {
+        self.flag.store(true, Ordering::SeqCst);
+        if let Some(sender) = &self.sender {
+            // Wake a parked dispatch so the flag is observed immediately rather
+            // than after the next event.
+            let _ = sender.send(());
+        }
+    }
+}
+
+/// A cloneable handle for reading and writing the clipboard through the worker
+/// thread, obtained from [`Master::clipboard_access`].
+///
+/// The handle talks to a dedicated worker thread that owns its own clipboard
+/// connection, so a handler can fetch or replace the new contents from inside
+/// a callback without the monitor's own connection blocking on the read.
+#[derive(Clone)]
+pub struct ClipboardAccess {
+    sender: channel::Sender<Command>,
+}
+
+impl ClipboardAccess {
+    /// Read the clipboard contents advertised as `mime`.
+    pub fn load(&self, mime: &str) -> io::Result<Vec<u8>> {
+        self.request(|reply| Command::Load(mime.to_string(), reply))
+    }
+
+    /// Read the primary-selection contents advertised as `mime`.
+    pub fn load_primary(&self, mime: &str) -> io::Result<Vec<u8>> {
+        self.request(|reply| Command::LoadPrimary(mime.to_string(), reply))
+    }
+
+    /// Take ownership of the clipboard, advertising `data` as `mime`.
+    pub fn store(&self, mime: &str, data: Vec<u8>) -> io::Result<()> {
+        self.request(|reply| Command::Store(mime.to_string(), data, reply))
+            .map(|_| ())
+    }
+
+    /// Take ownership of the primary selection, advertising `data` as `mime`.
+    pub fn store_primary(&self, mime: &str, data: Vec<u8>) -> io::Result<()> {
+        self.request(|reply| Command::StorePrimary(mime.to_string(), data, reply))
+            .map(|_| ())
+    }
+
+    fn request(
+        &self,
+        build: impl FnOnce(mpsc::Sender<io::Result<Vec<u8>>>) -> Command,
+    ) -> io::Result<Vec<u8>> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        self.sender
+            .send(build(reply_tx))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Clipboard worker is gone"))?;
+        reply_rx
+            .recv()
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Clipboard worker is gone"))?
+    }
+}
+
+/// Spawn the worker thread that owns a dedicated clipboard connection and
+/// serves [`Command`]s until it receives [`Command::Exit`].
+fn spawn_worker() -> io::Result<(ClipboardAccess, thread::JoinHandle<()>)> {
+    let flag = Arc::new(AtomicBool::new(false));
+    let mut listener = WlClipboardListener::init(flag)?;
+    listener.set_monitor_primary(true);
+
+    let (sender, commands) = channel::channel::<Command>();
+    let handle = thread::spawn(move || {
+        if let Err(e) = listener.run_worker(commands) {
+            eprintln!("Clipboard worker stopped: {e}");
+        }
+    });
+
+    Ok((ClipboardAccess { sender }, handle))
+}
+
+/// Monitors the clipboard and drives a [`ClipboardHandler`].
+pub struct Master<H> {
+    handler: H,
+    listener: WlClipboardListener,
+    flag: Arc<AtomicBool>,
+    access: Option<ClipboardAccess>,
+    worker: Option<thread::JoinHandle<()>>,
+}
+
+impl<H: ClipboardHandler> Master<H> {
+    /// Create a monitor bound to `handler`.
+    pub fn new(handler: H) -> io::Result<Self> {
+        let flag = Arc::new(AtomicBool::new(false));
+        let listener = WlClipboardListener::init(flag.clone())?;
+        Ok(Self {
+            handler,
+            listener,
+            flag,
+            access: None,
+            worker: None,
+        })
+    }
+
+    /// A cloneable [`ClipboardAccess`] handle for reading and writing the
+    /// clipboard, spawning the worker thread on first use.
+    ///
+    /// Obtain this before [`run`](Self::run) and clone it into the handler so a
+    /// callback can read or replace the new contents without opening its own
+    /// clipboard connection.
+    pub fn clipboard_access(&mut self) -> io::Result<ClipboardAccess> {
+        if let Some(access) = &self.access {
+            return Ok(access.clone());
+        }
+        let (access, worker) = spawn_worker()?;
+        self.access = Some(access.clone());
+        self.worker = Some(worker);
+        Ok(access)
+    }
+
+    /// Enable or disable monitoring of the primary (middle-click) selection.
+    ///
+    /// When enabled, primary changes are delivered to
+    /// [`ClipboardHandler::on_primary_selection_change`]; disabled by default.
+    pub fn monitor_primary(&mut self, enabled: bool) -> &mut Self {
+        self.listener.set_monitor_primary(enabled);
+        self
+    }
+
+    /// A [`Shutdown`] handle that stops [`run`](Self::run) from another thread.
+    pub fn shutdown_channel(&self) -> Shutdown {
+        Shutdown {
+            sender: self.listener.shutdown_sender(),
+            flag: self.flag.clone(),
+        }
+    }
+
+    /// Block the current thread, dispatching clipboard changes until a handler
+    /// stops the loop or [`Shutdown::signal`] is called.
+    pub fn run(&mut self) -> io::Result<()> {
+        loop {
+            let result = match self.listener.next() {
+                Some(Ok(message)) => match message.kind {
+                    SelectionKind::Clipboard => self
+                        .handler
+                        .on_clipboard_change_with_formats(&message.mime_types),
+                    SelectionKind::Primary => self.handler.on_primary_selection_change(),
+                },
+                Some(Err(error)) => {
+                    // A shutdown request surfaces as an error; treat a tripped
+                    // exit flag as a clean stop rather than a failure.
+                    if self.flag.load(Ordering::SeqCst) {
+                        return Ok(());
+                    }
+                    self.handler.on_clipboard_error(error)
+                }
+                None => return Ok(()),
+            };
+
+            match result {
+                CallbackResult::Next => continue,
+                CallbackResult::Stop => return Ok(()),
+                CallbackResult::StopWithError(error) => return Err(error),
+            }
+        }
+    }
+}
+
+impl<H> Drop for Master<H> {
+    fn drop(&mut self) {
+        if let Some(access) = self.access.take() {
+            let _ = access.sender.send(Command::Exit);
+        }
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
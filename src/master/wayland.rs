@@ -1,34 +1,90 @@
 // Derived from https://github.com/Decodetalkers/wayland-clipboard-listener/blob/master/src/dispatch.rs
 
 use std::{
-    io,
-    sync::{atomic::AtomicBool, Arc, Mutex},
+    collections::HashMap,
+    io::{self, Read, Write},
+    os::fd::AsFd,
+    sync::{atomic::AtomicBool, Arc},
 };
+use calloop::{channel, EventLoop};
+use calloop_wayland_source::WaylandSource;
 use wayland_client::{
-    backend::WaylandError,
     event_created_child,
     protocol::{wl_registry, wl_seat},
-    Connection, Dispatch, EventQueue, Proxy,
+    Connection, Dispatch, Proxy,
 };
 use wayland_protocols_wlr::data_control::v1::client::{
     zwlr_data_control_device_v1, zwlr_data_control_manager_v1, zwlr_data_control_offer_v1,
     zwlr_data_control_source_v1,
 };
 
+/// Which selection a [`ClipBoardListenMessage`] describes.
+///
+/// Wayland (and X11) expose the regular clipboard and the middle-click
+/// *primary* selection as two independent channels; callers that mirror a
+/// remote desktop need to tell them apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SelectionKind {
+    Clipboard,
+    Primary,
+}
+
+/// Which served-data map a data source we own draws from; attached as the
+/// source's user data so `Send` events reach the right selection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SourceSelection {
+    Clipboard,
+    Primary,
+}
+
 #[derive(Debug)]
 pub(crate) struct ClipBoardListenMessage {
+    pub kind: SelectionKind,
     pub mime_types: Vec<String>,
 }
 
+/// Reply channel carrying the outcome of a [`Command`]. `Store`/`StorePrimary`
+/// reply with an empty vector on success.
+pub(crate) type CommandReply = std::sync::mpsc::Sender<io::Result<Vec<u8>>>;
+
+/// A read/write request serviced by the clipboard access worker.
+pub(crate) enum Command {
+    /// Read the clipboard contents advertised as the given MIME type.
+    Load(String, CommandReply),
+    /// Take ownership of the clipboard, advertising the given MIME type.
+    Store(String, Vec<u8>, CommandReply),
+    /// Read the primary-selection contents for the given MIME type.
+    LoadPrimary(String, CommandReply),
+    /// Take ownership of the primary selection, advertising the given MIME type.
+    StorePrimary(String, Vec<u8>, CommandReply),
+    /// Tear the worker down.
+    Exit,
+}
+
 pub(crate) struct WlClipboardListener {
+    conn: Connection,
+    qh: wayland_client::QueueHandle<Self>,
     seat: Option<wl_seat::WlSeat>,
     seat_name: Option<String>,
     data_manager: Option<zwlr_data_control_manager_v1::ZwlrDataControlManagerV1>,
     data_device: Option<zwlr_data_control_device_v1::ZwlrDataControlDeviceV1>,
     mime_types: Vec<String>,
-    queue: Option<Arc<Mutex<EventQueue<Self>>>>,
+    /// The most recent clipboard / primary offers, kept so the read worker can
+    /// `receive` their data on demand instead of re-opening a connection.
+    clipboard_offer: Option<zwlr_data_control_offer_v1::ZwlrDataControlOfferV1>,
+    primary_offer: Option<zwlr_data_control_offer_v1::ZwlrDataControlOfferV1>,
+    /// Data served to requesting clients, keyed by MIME type, while we own the
+    /// regular clipboard (drained by `Send` events on our data source).
+    serve_clipboard: HashMap<String, Vec<u8>>,
+    /// As [`serve_clipboard`](Self::serve_clipboard), for the primary selection;
+    /// kept separate so storing one selection never drops the other's data.
+    serve_primary: HashMap<String, Vec<u8>>,
+    event_loop: Option<EventLoop<'static, WlClipboardListener>>,
+    shutdown_tx: Option<channel::Sender<()>>,
     exit_flag: Arc<AtomicBool>,
     copied: bool,
+    primary_copied: bool,
+    monitor_primary: bool,
 }
 
 impl WlClipboardListener {
@@ -45,14 +101,23 @@ impl WlClipboardListener {
 
         display.get_registry(&qhandle, ());
         let mut state = WlClipboardListener {
+            conn: conn.clone(),
+            qh: qhandle.clone(),
             seat: None,
             seat_name: None,
             data_manager: None,
             data_device: None,
             mime_types: Vec::new(),
-            queue: None,
+            clipboard_offer: None,
+            primary_offer: None,
+            serve_clipboard: HashMap::new(),
+            serve_primary: HashMap::new(),
+            event_loop: None,
+            shutdown_tx: None,
             exit_flag,
             copied: false,
+            primary_copied: false,
+            monitor_primary: false,
         };
         event_queue.blocking_dispatch(&mut state).map_err(|e| {
             io::Error::new(io::ErrorKind::Other, format!("Inital dispatch failed: {e}"))
@@ -70,10 +135,197 @@ impl WlClipboardListener {
         }
 
         state.set_data_device(&qhandle);
-        state.queue = Some(Arc::new(Mutex::new(event_queue)));
+
+        // Drive the Wayland connection through calloop instead of a hand-rolled
+        // `prepare_read`/`read` spin loop: the connection fd becomes an event
+        // source that is only dispatched when it is actually readable, and the
+        // shutdown channel is wired in so `signal()` wakes the loop immediately
+        // rather than after the next sleep tick.
+        let event_loop: EventLoop<'static, WlClipboardListener> = EventLoop::try_new()
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("Cannot create event loop: {e}")))?;
+        let handle = event_loop.handle();
+
+        WaylandSource::new(conn, event_queue)
+            .insert(handle.clone())
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("Cannot insert wayland source: {e}")))?;
+
+        let (shutdown_tx, shutdown_rx) = channel::channel::<()>();
+        let exit_flag = state.exit_flag.clone();
+        handle
+            .insert_source(shutdown_rx, move |_event, _metadata, _state| {
+                exit_flag.store(true, std::sync::atomic::Ordering::Relaxed);
+            })
+            .map_err(|e| {
+                io::Error::new(io::ErrorKind::Other, format!("Cannot insert shutdown channel: {e}"))
+            })?;
+
+        state.shutdown_tx = Some(shutdown_tx);
+        state.event_loop = Some(event_loop);
         Ok(state)
     }
 
+    /// A sender that wakes the dispatch loop for shutdown.
+    ///
+    /// Sending on it wakes the parked `dispatch` immediately; the next
+    /// [`get_message`](Self::get_message) iteration then observes the shared
+    /// exit flag and returns an error so the listener thread can unwind.
+    /// Setting the exit flag alone is *not* enough — `dispatch(None, …)` blocks
+    /// with no timeout, so the flag is only polled once a send (or a clipboard
+    /// event) has woken it.
+    pub(crate) fn shutdown_sender(&self) -> Option<channel::Sender<()>> {
+        self.shutdown_tx.clone()
+    }
+
+    /// Enable or disable monitoring of the primary (middle-click) selection.
+    ///
+    /// When disabled (the default) primary-selection offers are dropped, matching
+    /// the previous behaviour; when enabled they are surfaced as
+    /// [`SelectionKind::Primary`] messages.
+    pub(crate) fn set_monitor_primary(&mut self, enabled: bool) {
+        self.monitor_primary = enabled;
+    }
+
+    /// Run the read/write worker: service `commands` and the Wayland
+    /// connection fd from the same `calloop` loop until [`Command::Exit`].
+    ///
+    /// Driving both from one loop keeps the fd serviced continuously, so the
+    /// `Send` events that fulfil another application's paste are processed even
+    /// when the only traffic is `Store`/`StorePrimary` — a read is no longer
+    /// required to pump the queue.
+    pub(crate) fn run_worker(mut self, commands: channel::Channel<Command>) -> io::Result<()> {
+        let mut event_loop = self.event_loop.take().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Other, "Event loop not initialized")
+        })?;
+        event_loop
+            .handle()
+            .insert_source(commands, |event, _metadata, state: &mut WlClipboardListener| {
+                if let channel::Event::Msg(command) = event {
+                    state.handle_command(command);
+                }
+            })
+            .map_err(|e| {
+                io::Error::new(io::ErrorKind::Other, format!("Cannot insert command channel: {e}"))
+            })?;
+
+        while !self.exit_flag.load(std::sync::atomic::Ordering::Relaxed) {
+            if let Err(e) = event_loop.dispatch(None, &mut self) {
+                self.event_loop = Some(event_loop);
+                return Err(io::Error::new(io::ErrorKind::Other, format!("Dispatch failed: {e}")));
+            }
+        }
+        self.event_loop = Some(event_loop);
+        Ok(())
+    }
+
+    /// Service a single worker [`Command`], replying on its channel.
+    fn handle_command(&mut self, command: Command) {
+        match command {
+            Command::Load(mime, reply) => {
+                let _ = reply.send(self.read_clipboard(&mime));
+            }
+            Command::LoadPrimary(mime, reply) => {
+                let _ = reply.send(self.read_primary(&mime));
+            }
+            Command::Store(mime, data, reply) => {
+                let _ = reply.send(self.store_clipboard(&mime, data).map(|_| Vec::new()));
+            }
+            Command::StorePrimary(mime, data, reply) => {
+                let _ = reply.send(self.store_primary(&mime, data).map(|_| Vec::new()));
+            }
+            Command::Exit => {
+                self.exit_flag
+                    .store(true, std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Read the bytes currently advertised on the clipboard in `mime`.
+    pub(crate) fn read_clipboard(&self, mime: &str) -> io::Result<Vec<u8>> {
+        let offer = self.clipboard_offer.as_ref().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "No clipboard offer available")
+        })?;
+        self.read_offer(offer, mime)
+    }
+
+    /// Read the bytes currently advertised on the primary selection in `mime`.
+    pub(crate) fn read_primary(&self, mime: &str) -> io::Result<Vec<u8>> {
+        let offer = self.primary_offer.as_ref().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "No primary offer available")
+        })?;
+        self.read_offer(offer, mime)
+    }
+
+    /// Pull an offer's data for `mime` through a pipe, draining it to EOF.
+    fn read_offer(
+        &self,
+        offer: &zwlr_data_control_offer_v1::ZwlrDataControlOfferV1,
+        mime: &str,
+    ) -> io::Result<Vec<u8>> {
+        let (reader, writer) = rustix::pipe::pipe()
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("Cannot create pipe: {e}")))?;
+        offer.receive(mime.to_string(), writer.as_fd());
+        // Drop our copy of the write end so we see EOF once the sender is done.
+        drop(writer);
+        self.conn
+            .flush()
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("Flush failed: {e}")))?;
+        let mut buf = Vec::new();
+        std::fs::File::from(reader).read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Take ownership of the clipboard, advertising `mime` and serving `data`.
+    pub(crate) fn store_clipboard(&mut self, mime: &str, data: Vec<u8>) -> io::Result<()> {
+        self.store(mime, data, false)
+    }
+
+    /// Take ownership of the primary selection, advertising `mime` and serving `data`.
+    pub(crate) fn store_primary(&mut self, mime: &str, data: Vec<u8>) -> io::Result<()> {
+        self.store(mime, data, true)
+    }
+
+    fn store(&mut self, mime: &str, data: Vec<u8>, primary: bool) -> io::Result<()> {
+        let which = if primary {
+            SourceSelection::Primary
+        } else {
+            SourceSelection::Clipboard
+        };
+        let source = {
+            let manager = self.data_manager.as_ref().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::Other, "Data manager not available")
+            })?;
+            manager.create_data_source(&self.qh, which)
+        };
+        source.offer(mime.to_string());
+        // Drop data advertised by a previous store of *this* selection; the new
+        // source only offers `mime`, so keeping stale entries would serve an
+        // outdated format. The other selection's served data is left intact.
+        let serve = self.serve_map(which);
+        serve.clear();
+        serve.insert(mime.to_string(), data);
+        let device = self
+            .data_device
+            .as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Data device not available"))?;
+        if primary {
+            device.set_primary_selection(Some(&source));
+        } else {
+            device.set_selection(Some(&source));
+        }
+        self.conn
+            .flush()
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("Flush failed: {e}")))?;
+        Ok(())
+    }
+
+    /// The served-data map for `which` selection.
+    fn serve_map(&mut self, which: SourceSelection) -> &mut HashMap<String, Vec<u8>> {
+        match which {
+            SourceSelection::Clipboard => &mut self.serve_clipboard,
+            SourceSelection::Primary => &mut self.serve_primary,
+        }
+    }
+
     fn device_ready(&self) -> bool {
         self.seat.is_some() && self.data_manager.is_some()
     }
@@ -89,56 +341,45 @@ impl WlClipboardListener {
     }
 
     fn get_message(&mut self) -> Result<ClipBoardListenMessage, io::Error> {
-        let Some(queue) = self.queue.clone() else {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "Event queue not initialized",
-            ));
-        };
-        let mut queue = queue
-            .lock()
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("Cannot lock queue: {e}")))?;
-        loop {
+        // Take the loop out of `self` so we can pass `self` as the dispatch
+        // state; it is restored before returning.
+        let mut event_loop = self.event_loop.take().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Other, "Event loop not initialized")
+        })?;
+        let kind = loop {
             if self.exit_flag.load(std::sync::atomic::Ordering::Relaxed) {
+                self.event_loop = Some(event_loop);
                 return Err(io::Error::new(
                     io::ErrorKind::Other,
                     "Exit signal received, exiting",
                 ));
             }
 
-            queue
-                .flush()
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("Flush failed: {e}")))?;
-            let read_guard = queue.prepare_read().map_err(|e| {
-                io::Error::new(io::ErrorKind::Other, format!("Prepare read failed: {e}"))
-            })?;
-            match read_guard.read() {
-                Ok(c) => {
-                    if c > 0 {
-                        queue.dispatch_pending(self).map_err(|e| {
-                            io::Error::new(
-                                io::ErrorKind::Other,
-                                format!("Dispatch pending failed: {e}"),
-                            )
-                        })?;
-                        if self.copied {
-                            self.copied = false;
-                            break;
-                        }
-                    }
-                }
-                Err(WaylandError::Io(ref e)) if e.kind() == io::ErrorKind::WouldBlock => {
-                    std::thread::sleep(std::time::Duration::from_millis(100));
-                }
-                Err(e) => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        format!("Read failed: {e}"),
-                    ));
-                }
+            // Report a change left pending by an earlier dispatch (e.g. a primary
+            // change that arrived together with a clipboard change) before
+            // blocking again.
+            if self.copied {
+                self.copied = false;
+                break SelectionKind::Clipboard;
             }
-        }
+            if self.primary_copied {
+                self.primary_copied = false;
+                break SelectionKind::Primary;
+            }
+
+            // Block until the connection fd is readable or the shutdown channel
+            // fires — no fixed-latency sleep, no spin.
+            if let Err(e) = event_loop.dispatch(None, self) {
+                self.event_loop = Some(event_loop);
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("Dispatch failed: {e}"),
+                ));
+            }
+        };
+        self.event_loop = Some(event_loop);
         Ok(ClipBoardListenMessage {
+            kind,
             mime_types: self.mime_types.clone(),
         })
     }
@@ -222,12 +463,17 @@ impl Dispatch<zwlr_data_control_device_v1::ZwlrDataControlDeviceV1, ()> for WlCl
         qh: &wayland_client::QueueHandle<Self>,
     ) {
         match event {
-            zwlr_data_control_device_v1::Event::DataOffer { id: _id } => {}
+            zwlr_data_control_device_v1::Event::DataOffer { id: _id } => {
+                // A new offer supersedes the formats advertised by the
+                // previous one; drop the stale set so the list returned in
+                // `ClipBoardListenMessage` describes only the current offer.
+                state.mime_types.clear();
+            }
             zwlr_data_control_device_v1::Event::Finished => {
                 if let Some(source) = state
                     .data_manager
                     .as_ref()
-                    .map(|dm| dm.create_data_source(qh, ()))
+                    .map(|dm| dm.create_data_source(qh, SourceSelection::Clipboard))
                 {
                     state
                         .data_device
@@ -237,14 +483,23 @@ impl Dispatch<zwlr_data_control_device_v1::ZwlrDataControlDeviceV1, ()> for WlCl
             }
             zwlr_data_control_device_v1::Event::PrimarySelection { id } => {
                 if let Some(offer) = id {
-                    offer.destroy();
+                    // Treat the primary selection symmetrically to the regular
+                    // one when monitoring is enabled; otherwise keep the old
+                    // behaviour of discarding the offer.
+                    if state.monitor_primary {
+                        state.primary_copied = true;
+                        state.primary_offer = Some(offer);
+                    } else {
+                        offer.destroy();
+                    }
                 }
             }
             zwlr_data_control_device_v1::Event::Selection { id } => {
-                let Some(_offer) = id else {
+                let Some(offer) = id else {
                     return;
                 };
                 state.copied = true;
+                state.clipboard_offer = Some(offer);
             }
             _ => {
                 println!("unhandled event: {:?}", event);
@@ -256,20 +511,29 @@ impl Dispatch<zwlr_data_control_device_v1::ZwlrDataControlDeviceV1, ()> for WlCl
     ]);
 }
 
-impl Dispatch<zwlr_data_control_source_v1::ZwlrDataControlSourceV1, ()> for WlClipboardListener {
+impl Dispatch<zwlr_data_control_source_v1::ZwlrDataControlSourceV1, SourceSelection>
+    for WlClipboardListener
+{
     fn event(
-        _state: &mut Self,
+        state: &mut Self,
         _proxy: &zwlr_data_control_source_v1::ZwlrDataControlSourceV1,
         event: <zwlr_data_control_source_v1::ZwlrDataControlSourceV1 as Proxy>::Event,
-        _data: &(),
+        which: &SourceSelection,
         _conn: &Connection,
         _qhandle: &wayland_client::QueueHandle<Self>,
     ) {
         match event {
-            zwlr_data_control_source_v1::Event::Send {
-                fd: _fd,
-                mime_type: _mime_type,
-            } => {}
+            zwlr_data_control_source_v1::Event::Send { fd, mime_type } => {
+                // A client asked for the data we advertised via `store`; write it
+                // to the supplied pipe, drawing from the map for this source's
+                // selection. Errors are non-fatal — the peer may have hung up —
+                // so they are only logged.
+                if let Some(data) = state.serve_map(*which).get(&mime_type) {
+                    if let Err(e) = std::fs::File::from(fd).write_all(data) {
+                        eprintln!("Failed to serve clipboard data for {mime_type}: {e}");
+                    }
+                }
+            }
             _ => {
                 eprintln!("unhandled event: {event:?}");
             }